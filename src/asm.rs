@@ -0,0 +1,212 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2017 by Kevin Läufer <kevin.laeufer@rwth-aachen.de>
+
+// A small two-pass assembler turning EDSAC Jr source text into a memory
+// image, so programs no longer have to be hand-assembled with the
+// `add`/`sub`/`store`/`con` helpers in `main`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::{mask, Instruction, Integer, INSTRUCTION_TYPES};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsmError {
+	UnknownMnemonic { line: usize, mnemonic: String },
+	UndefinedLabel { line: usize, label: String },
+	InvalidOperand { line: usize, text: String },
+	OperandOutOfRange { line: usize, value: i32 },
+}
+
+impl fmt::Display for AsmError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			AsmError::UnknownMnemonic { line, mnemonic } =>
+				write!(f, "line {}: unknown mnemonic `{}`", line, mnemonic),
+			AsmError::UndefinedLabel { line, label } =>
+				write!(f, "line {}: undefined label `{}`", line, label),
+			AsmError::InvalidOperand { line, text } =>
+				write!(f, "line {}: invalid operand `{}`", line, text),
+			AsmError::OperandOutOfRange { line, value } =>
+				write!(f, "line {}: operand {} is out of range", line, value),
+		}
+	}
+}
+
+// One non-empty, non-comment source line after its label (if any) has
+// been stripped off.
+struct ParsedLine<'a> {
+	line_no: usize,
+	mnemonic: &'a str,
+	operand: Option<&'a str>,
+}
+
+fn strip_comment(line: &str) -> &str {
+	match line.find(';') {
+		Some(idx) => &line[..idx],
+		None => line,
+	}
+}
+
+// Assembles EDSAC Jr source text (mnemonic lines like `ADD 5`, `STORE x`,
+// `BLT loop`, `CON -30`, optionally preceded by a `label:`) into a memory
+// image. Returns the first error encountered rather than panicking.
+pub fn assemble(src: &str) -> Result<Vec<u16>, AsmError> {
+	let mut parsed: Vec<ParsedLine> = Vec::new();
+	let mut symbols: HashMap<String, u16> = HashMap::new();
+	let mut addr: u16 = 0;
+
+	// pass 1: assign addresses and record label definitions
+	for (idx, raw_line) in src.lines().enumerate() {
+		let line_no = idx + 1;
+		let code = strip_comment(raw_line).trim();
+		if code.is_empty() {
+			continue;
+		}
+
+		let (label, rest) = match code.find(':') {
+			Some(pos) => (Some(code[..pos].trim()), code[pos + 1..].trim()),
+			None => (None, code),
+		};
+		if let Some(label) = label {
+			symbols.insert(label.to_string(), addr);
+		}
+		if rest.is_empty() {
+			continue; // label-only line, does not occupy a word
+		}
+
+		let mut parts = rest.splitn(2, char::is_whitespace);
+		let mnemonic = parts.next().unwrap_or("").trim();
+		let operand = parts.next().map(|s| s.trim()).filter(|s| !s.is_empty());
+		parsed.push(ParsedLine { line_no, mnemonic, operand });
+		addr += 1;
+	}
+
+	// pass 2: resolve operands and emit words
+	let mut words = Vec::with_capacity(parsed.len());
+	for line in &parsed {
+		words.push(assemble_line(line, &symbols)?);
+	}
+	Ok(words)
+}
+
+fn assemble_line(line: &ParsedLine, symbols: &HashMap<String, u16>) -> Result<u16, AsmError> {
+	if line.mnemonic.eq_ignore_ascii_case("CON") {
+		let text = line.operand.ok_or_else(|| AsmError::InvalidOperand {
+			line: line.line_no,
+			text: String::new(),
+		})?;
+		let value: i16 = text.parse().map_err(|_| AsmError::InvalidOperand {
+			line: line.line_no,
+			text: text.to_string(),
+		})?;
+		// The sign-magnitude format can only represent up to -32767;
+		// `i16::MIN` would silently wrap into negative zero below.
+		if value == i16::MIN {
+			return Err(AsmError::OperandOutOfRange { line: line.line_no, value: value as i32 });
+		}
+		return Ok(Integer::new(value).w);
+	}
+
+	let instr_type = INSTRUCTION_TYPES
+		.iter()
+		.find(|tt| tt.name.eq_ignore_ascii_case(line.mnemonic))
+		.ok_or_else(|| AsmError::UnknownMnemonic {
+			line: line.line_no,
+			mnemonic: line.mnemonic.to_string(),
+		})?;
+
+	let n = match line.operand {
+		None => 0,
+		Some(text) => resolve_operand(line.line_no, text, symbols)?,
+	};
+	Ok(Instruction::new(instr_type.opcode, n).w)
+}
+
+fn resolve_operand(line_no: usize, text: &str, symbols: &HashMap<String, u16>) -> Result<u16, AsmError> {
+	let value: i32 = match text.parse::<i32>() {
+		Ok(v) => v,
+		Err(_) => *symbols.get(text).ok_or_else(|| AsmError::UndefinedLabel {
+			line: line_no,
+			label: text.to_string(),
+		})? as i32,
+	};
+	if value < 0 || value > mask(11) as i32 {
+		return Err(AsmError::OperandOutOfRange { line: line_no, value });
+	}
+	Ok(value as u16)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{ADD, BLT, END, STORE};
+
+	#[test]
+	fn assembles_labels_resolved_in_both_directions() {
+		// `loop:` is resolved forward by `BLT loop` referring back to it,
+		// and `done` is resolved by a label defined after its use.
+		let src = "\
+			loop: ADD count\n\
+			\tSTORE count\n\
+			\tBLT loop\n\
+			\tBLT done\n\
+			done: END\n\
+			count: CON -3\n";
+		let image = assemble(src).unwrap();
+		assert_eq!(image, vec![
+			Instruction::encode(ADD.opcode, 5),
+			Instruction::encode(STORE.opcode, 5),
+			Instruction::encode(BLT.opcode, 0),
+			Instruction::encode(BLT.opcode, 4),
+			Instruction::encode(END.opcode, 0),
+			Integer::new(-3).bits(),
+		]);
+	}
+
+	#[test]
+	fn rejects_an_unknown_mnemonic() {
+		let err = assemble("FROB 1\n").unwrap_err();
+		assert_eq!(err, AsmError::UnknownMnemonic { line: 1, mnemonic: "FROB".to_string() });
+	}
+
+	#[test]
+	fn rejects_a_branch_to_an_undefined_label() {
+		let err = assemble("BLT nowhere\n").unwrap_err();
+		assert_eq!(err, AsmError::UndefinedLabel { line: 1, label: "nowhere".to_string() });
+	}
+
+	#[test]
+	fn rejects_an_operand_that_does_not_fit_the_11_bit_field() {
+		let err = assemble("STORE 2048\n").unwrap_err();
+		assert_eq!(err, AsmError::OperandOutOfRange { line: 1, value: 2048 });
+	}
+
+	#[test]
+	fn con_accepts_negative_literals_outside_the_11_bit_field() {
+		// CON packs a full signed `Integer`, not an 11-bit operand, so
+		// this must not be rejected by the 11-bit field check above.
+		let image = assemble("CON -30\n").unwrap();
+		assert_eq!(image, vec![Integer::new(-30).bits()]);
+	}
+
+	#[test]
+	fn con_rejects_i16_min_instead_of_wrapping_to_negative_zero() {
+		// i16::MIN (-32768) has no sign-magnitude representation; the
+		// closest in-range value is -32767.
+		let err = assemble("CON -32768\n").unwrap_err();
+		assert_eq!(err, AsmError::OperandOutOfRange { line: 1, value: -32768 });
+	}
+}
@@ -0,0 +1,420 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2017 by Kevin Läufer <kevin.laeufer@rwth-aachen.de>
+
+// The instruction set, sign-magnitude `Integer` arithmetic and the
+// `Machine` execution engine build on `core` alone, so this crate can be
+// embedded in constrained or embedded contexts that have no `std`. The
+// `println!`-driven demo in `src/main.rs`, the text assembler and the
+// `Program` (de)serialization format all need an allocator and I/O, so
+// they live behind the default `std` feature instead.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::fmt;
+use core::ops;
+
+#[cfg(feature = "std")]
+pub mod asm;
+pub mod machine;
+#[cfg(feature = "std")]
+pub mod program;
+
+pub const MAX_MEM: usize = 2048;
+fn mask(bits: u32) -> u16 { (1 << bits) - 1 }
+
+// Errors that can occur while decoding or executing a program. These are
+// returned rather than panicking so that embedding the simulator in a
+// larger tool (or a test harness) never aborts the process on malformed
+// memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+	UnknownOpcode(u16),
+	Overflow { lhs: Integer, rhs: Integer },
+	AddressOutOfRange(u16),
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::UnknownOpcode(opcode) => write!(f, "unknown op code ({:05b})", opcode),
+			Error::Overflow { lhs, rhs } => write!(f, "overflow detected trying to execute {} + {}", lhs, rhs),
+			Error::AddressOutOfRange(addr) => write!(f, "address {} is out of range", addr),
+		}
+	}
+}
+
+// Machine State
+#[derive(Clone, Copy)]
+struct Regs { acc: Integer, pc: u16 }
+
+impl Regs {
+	fn new(pc: u16) -> Regs {
+		Regs {acc: Integer::new(0), pc}
+	}
+}
+impl fmt::Display for Regs {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "pc: {:04}; acc: {:>6}", self.pc, self.acc)
+	}
+}
+
+
+// Instruction Definitions
+#[allow(unused)]
+struct InstrType { name: &'static str, opcode: u16, exec: fn(u16, Regs, &mut[u16]) -> Result<Regs, Error> }
+
+// unfortunately the synthax for generating function pointers on the fly
+// is somewhat difficult to read for now, this should be fixed once the
+// following change is merged: https://github.com/rust-lang/rfcs/pull/1558
+const ADD:    InstrType = InstrType { name: "ADD",    opcode: 0b00001,
+exec: { fn exec(n: u16, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+	let acc = old.acc.try_add(Integer::try_load(n, mem)?)?;
+	Ok(Regs { acc, pc: old.pc + 1})
+	} exec } };
+
+const SUB:    InstrType = InstrType { name: "SUB",    opcode: 0b10000,
+exec: { fn exec(n: u16, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+	let acc = old.acc.try_sub(Integer::try_load(n, mem)?)?;
+	Ok(Regs { acc, pc: old.pc + 1})
+	} exec } };
+
+const STORE:  InstrType = InstrType { name: "STORE",  opcode: 0b00010,
+exec: { fn exec(n: u16, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+	let addr = n as usize;
+	if addr >= mem.len() { return Err(Error::AddressOutOfRange(n)); }
+	mem[addr] = old.acc.w;
+	Ok(Regs { acc: old.acc, pc: old.pc + 1})
+	} exec } };
+
+const CLEAR:  InstrType = InstrType { name: "CLEAR",  opcode: 0b00011,
+exec: { fn exec(_: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: Integer::new(0), pc: old.pc + 1})
+	} exec } };
+
+const OR:     InstrType = InstrType { name: "OR",     opcode: 0b00000,
+exec: { fn exec(n: u16, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc | Integer::try_load(n, mem)?, pc: old.pc + 1})
+	} exec } };
+
+const AND:    InstrType = InstrType { name: "AND",    opcode: 0b00100,
+exec: { fn exec(n: u16, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc & Integer::try_load(n, mem)?, pc: old.pc + 1})
+	} exec } };
+
+const SHIFTR: InstrType = InstrType { name: "SHIFTR", opcode: 0b00101,
+exec: { fn exec(n: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc >> n, pc: old.pc + 1})
+	} exec } };
+
+const SHIFTL: InstrType = InstrType { name: "SHIFTL", opcode: 0b00110,
+exec: { fn exec(n: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc << n, pc: old.pc + 1})
+	} exec } };
+
+const BGE:    InstrType = InstrType { name: "BGE",    opcode: 0b00111,
+exec: { fn exec(n: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc, pc: if !old.acc.less_than_zero() { n } else { old.pc + 1} })
+	} exec } };
+
+const BLT:    InstrType = InstrType { name: "BLT",    opcode: 0b01000,
+exec: { fn exec(n: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	Ok(Regs { acc: old.acc, pc: if old.acc.less_than_zero() { n } else { old.pc + 1} })
+	} exec } };
+
+const END:    InstrType = InstrType { name: "END",    opcode: 0b01010,
+exec: { fn exec(_: u16, old: Regs, _: &mut[u16]) -> Result<Regs, Error> {
+	// Halting itself is handled by `Machine::step`, which checks
+	// `instr.op() == END.opcode` after running this exec function.
+	Ok(Regs { acc: old.acc, pc: old.pc})
+	} exec } };
+
+
+static INSTRUCTION_TYPES: [InstrType; 11] =
+	[ADD, SUB, STORE, CLEAR, OR, AND, SHIFTR, SHIFTL, BGE, BLT, END];
+
+impl InstrType {
+	fn make(&self, n: u16) -> Instruction {
+		Instruction::new(self.opcode, n)
+	}
+}
+
+// Instruction represents an actual instruction in the program
+pub struct Instruction { w: u16 }
+
+impl Instruction {
+	// Packs an opcode and an (up to 11-bit) operand into a raw word.
+	pub fn encode(op: u16, n: u16) -> u16 {
+		(op << 11) | (n & mask(11))
+	}
+	// Splits a raw word back into its opcode and operand fields.
+	pub fn decode(w: u16) -> (u16, u16) {
+		(w >> 11, w & mask(11))
+	}
+	fn new(op: u16, n: u16) -> Instruction {
+		Instruction { w: Instruction::encode(op, n) }
+	}
+	pub fn from_bits(w: u16) -> Instruction {
+		Instruction { w }
+	}
+	pub fn bits(&self) -> u16 {
+		self.w
+	}
+	pub fn load(pc: u16, mem: &[u16]) -> Instruction {
+		Instruction::try_load(pc, mem).expect("address out of range")
+	}
+	pub fn try_load(pc: u16, mem: &[u16]) -> Result<Instruction, Error> {
+		mem.get(pc as usize)
+			.map(|w| Instruction { w: *w })
+			.ok_or(Error::AddressOutOfRange(pc))
+	}
+	fn try_get_type(&self) -> Result<&'static InstrType, Error> {
+		let opcode = self.op();
+		INSTRUCTION_TYPES.iter()
+			.find(|tt| tt.opcode == opcode)
+			.ok_or(Error::UnknownOpcode(opcode))
+	}
+	pub fn n(&self) -> u16 {
+		Instruction::decode(self.w).1
+	}
+	pub fn op(&self) -> u16 {
+		Instruction::decode(self.w).0
+	}
+	fn exec(&self, old: Regs, mem: &mut[u16]) -> Result<Regs, Error> {
+		(self.try_get_type()?.exec)(self.n(), old, mem)
+	}
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.try_get_type() {
+			Ok(tt) => write!(f, "{} {}", tt.name, self.n()),
+			Err(_) => write!(f, "??? {}", self.n()),
+		}
+	}
+}
+
+
+// 16bit Integer format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Integer { w: u16}
+impl Integer {
+	pub fn new(ii: i16) -> Integer {
+		let abs_val = ii.unsigned_abs() & mask(15);
+		if ii >= 0 { Integer { w: abs_val } }
+		else { Integer { w: (1<<15) | abs_val } }
+	}
+	pub fn from_bits(w: u16) -> Integer {
+		Integer { w }
+	}
+	pub fn bits(&self) -> u16 {
+		self.w
+	}
+	pub fn load(index: u16, mem: &[u16]) -> Integer {
+		Integer::try_load(index, mem).expect("address out of range")
+	}
+	pub fn try_load(index: u16, mem: &[u16]) -> Result<Integer, Error> {
+		mem.get(index as usize)
+			.map(|w| Integer { w: *w })
+			.ok_or(Error::AddressOutOfRange(index))
+	}
+	pub fn is_positive(&self) -> bool { self.w & (1 << 15) == 0}
+	pub fn abs(&self) -> u16 { self.w & mask(15) }
+	fn sign(&self) -> u16 { self.w & (1<<15) }
+	pub fn less_than_zero(&self) -> bool { !self.is_positive() && self.abs() != 0 }
+	fn try_add(self, other: Integer) -> Result<Integer, Error> {
+		if self.is_positive() == other.is_positive() {
+			let result = self.abs() as u32 + other.abs() as u32;
+			if result > mask(15) as u32 {
+				return Err(Error::Overflow { lhs: self, rhs: other });
+			}
+			// Normalize negative zero (e.g. -0 + -0) to positive zero so
+			// comparisons and `less_than_zero` behave consistently.
+			let sign = if result == 0 { 0 } else { self.sign() };
+			Ok(Integer { w: result as u16 | sign })
+		} else {
+			let pos = if self.is_positive() { self.abs()  } else { other.abs() };
+			let neg = if self.is_positive() { other.abs() } else { self.abs() };
+			if pos >= neg {
+				Ok(Integer { w: pos - neg })
+			} else {
+				Ok(Integer { w: (neg - pos) | (1 << 15) })
+			}
+		}
+	}
+	fn try_sub(self, other: Integer) -> Result<Integer, Error> {
+		self.try_add(Integer { w: other.w ^ (1<<15) })
+	}
+}
+
+impl fmt::Display for Integer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}{}", if self.is_positive() { "" } else { "-" }, self.abs())
+	}
+}
+
+impl ops::Add for Integer {
+	type Output = Integer;
+	fn add(self, other: Integer) -> Integer {
+		self.try_add(other).expect("overflow")
+	}
+}
+
+impl ops::Sub for Integer {
+	type Output = Integer;
+	fn sub(self, other: Integer) -> Integer {
+		self.try_sub(other).expect("overflow")
+	}
+}
+
+impl ops::BitOr for Integer {
+	type Output = Integer;
+	fn bitor(self, other: Integer) -> Integer {
+		Integer { w: self.w | other.w }
+	}
+}
+
+impl ops::BitAnd for Integer {
+	type Output = Integer;
+	fn bitand(self, other: Integer) -> Integer {
+		Integer { w: self.w & other.w }
+	}
+}
+
+impl ops::Shr<u16> for Integer {
+	type Output = Integer;
+	fn shr(self, other: u16) -> Integer {
+		// `other` is a raw 11-bit instruction operand (0-2047), not a
+		// guaranteed-in-range shift amount, so a native `>>` on the 15-bit
+		// magnitude would panic ("shift amount overflow") for `other >= 15`.
+		// Any such shift empties the magnitude entirely.
+		let mag = if other >= 15 { 0 } else { self.abs() >> other };
+		Integer { w: mag | self.sign() }
+	}
+}
+
+impl ops::Shl<u16> for Integer {
+	type Output = Integer;
+	fn shl(self, other: u16) -> Integer {
+		let mag = if other >= 15 { 0 } else { (self.abs() << other) & mask(15) };
+		Integer { w: mag | self.sign() }
+	}
+}
+
+// Small helpers for building raw memory words without going through the
+// text assembler, mirroring the mnemonics they encode.
+pub fn add(n: u16)   -> u16 { ADD.make(n).w }
+pub fn sub(n: u16)   -> u16 { SUB.make(n).w }
+pub fn store(n: u16) -> u16 { STORE.make(n).w }
+pub fn clear()       -> u16 { CLEAR.make(0).w }
+pub fn or(n: u16)    -> u16 { OR.make(n).w }
+pub fn and(n: u16)   -> u16 { AND.make(n).w }
+pub fn end()         -> u16 { END.make(0).w }
+pub fn con(n: i16)   -> u16 { Integer::new(n).w }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn opcodes_round_trip_through_encode_decode() {
+		for tt in INSTRUCTION_TYPES.iter() {
+			for n in 0u16..=2047 {
+				let w = Instruction::encode(tt.opcode, n);
+				let (op, decoded_n) = Instruction::decode(w);
+				assert_eq!(op, tt.opcode);
+				assert_eq!(decoded_n, n);
+			}
+		}
+	}
+
+	fn all_integers() -> impl Iterator<Item = Integer> {
+		(0i32..=32767).flat_map(|mag| {
+			let mag = mag as i16;
+			[Integer::new(mag), Integer::new(-mag)]
+		})
+	}
+
+	fn negate(a: Integer) -> Integer {
+		Integer::from_bits(a.bits() ^ (1 << 15))
+	}
+
+	// Signed value an `Integer` represents, independent of its bit layout,
+	// so tests can check results against plain arithmetic rather than
+	// against the same sign-flip the implementation itself uses.
+	fn to_i32(a: Integer) -> i32 {
+		if a.is_positive() { a.abs() as i32 } else { -(a.abs() as i32) }
+	}
+
+	#[test]
+	fn a_plus_negated_a_is_positive_zero() {
+		for a in all_integers() {
+			let sum = a + negate(a);
+			assert_eq!(sum, Integer::new(0));
+			assert!(sum.is_positive());
+		}
+	}
+
+	#[test]
+	fn a_minus_b_matches_plain_subtraction() {
+		for a in all_integers().step_by(997) {
+			for b in all_integers().step_by(1009) {
+				let expected = to_i32(a) - to_i32(b);
+				match a.try_sub(b) {
+					Ok(diff) => assert_eq!(to_i32(diff), expected),
+					Err(Error::Overflow { .. }) => assert!(expected.abs() > i16::MAX as i32),
+					Err(e) => panic!("unexpected error: {}", e),
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn negative_zero_normalizes_to_positive_zero() {
+		let neg_zero = negate(Integer::new(0));
+		assert_eq!(neg_zero + neg_zero, Integer::new(0));
+		assert!((neg_zero + neg_zero).is_positive());
+		assert_eq!(Integer::new(5) - Integer::new(5), Integer::new(0));
+		assert!((Integer::new(5) - Integer::new(5)).is_positive());
+	}
+
+	#[test]
+	fn shifts_never_corrupt_the_sign_bit() {
+		for a in all_integers() {
+			for shift in 0..15u16 {
+				assert_eq!((a << shift).is_positive(), a.is_positive());
+				assert_eq!((a >> shift).is_positive(), a.is_positive());
+			}
+		}
+	}
+
+	#[test]
+	fn shifts_at_or_beyond_the_15_bit_magnitude_empty_it_instead_of_panicking() {
+		// `shift` here is a raw 11-bit instruction operand (0-2047), as
+		// `SHIFTL`/`SHIFTR` hand it straight from user-assembled source, not
+		// a value already known to be a safe native shift amount.
+		for a in all_integers().step_by(997) {
+			for shift in [15u16, 16, 31, 100, 2047] {
+				let shifted_left = a << shift;
+				let shifted_right = a >> shift;
+				assert_eq!(shifted_left.abs(), 0);
+				assert_eq!(shifted_right.abs(), 0);
+				assert_eq!(shifted_left.is_positive(), a.is_positive());
+				assert_eq!(shifted_right.is_positive(), a.is_positive());
+			}
+		}
+	}
+}
@@ -0,0 +1,155 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2017 by Kevin Läufer <kevin.laeufer@rwth-aachen.de>
+
+// A `Machine` owns the registers and memory of a running program and lets
+// a front-end (debugger, test harness, ...) single-step it and observe
+// the result, instead of being hard-coupled to `println!` like the
+// original `run` loop.
+
+use super::{Error, Instruction, Integer, Regs, MAX_MEM, END};
+
+// The outcome of a single `Machine::step`.
+pub enum StepResult {
+	Ran(Instruction),
+	Halted,
+	Error(Error),
+}
+
+// The outcome of running a `Machine` until it halts or is stopped early.
+pub enum RunResult {
+	Halted,
+	Breakpoint(u16),
+	Error(Error),
+}
+
+pub struct Machine {
+	regs: Regs,
+	mem: [u16; MAX_MEM],
+	halted: bool,
+	breakpoints: [bool; MAX_MEM],
+}
+
+impl Machine {
+	pub fn new(pc: u16) -> Machine {
+		Machine {
+			regs: Regs::new(pc),
+			mem: [0; MAX_MEM],
+			halted: false,
+			breakpoints: [false; MAX_MEM],
+		}
+	}
+
+	pub fn acc(&self) -> Integer { self.regs.acc }
+	pub fn pc(&self) -> u16 { self.regs.pc }
+	pub fn is_halted(&self) -> bool { self.halted }
+	pub fn memory(&self) -> &[u16] { &self.mem }
+	pub fn memory_mut(&mut self) -> &mut [u16] { &mut self.mem }
+
+	pub fn set_breakpoint(&mut self, addr: u16) -> Result<(), Error> {
+		match self.breakpoints.get_mut(addr as usize) {
+			Some(bp) => { *bp = true; Ok(()) }
+			None => Err(Error::AddressOutOfRange(addr)),
+		}
+	}
+
+	// Executes a single instruction. Once `END` has run, the machine is
+	// halted and further calls are no-ops that return `Halted` instead of
+	// spinning on the same `pc`.
+	pub fn step(&mut self) -> StepResult {
+		if self.halted {
+			return StepResult::Halted;
+		}
+
+		let instr = match Instruction::try_load(self.regs.pc, &self.mem) {
+			Ok(instr) => instr,
+			Err(e) => return StepResult::Error(e),
+		};
+		match instr.exec(self.regs, &mut self.mem) {
+			Ok(new_regs) => {
+				self.regs = new_regs;
+				if instr.op() == END.opcode {
+					self.halted = true;
+				}
+				StepResult::Ran(instr)
+			}
+			Err(e) => StepResult::Error(e),
+		}
+	}
+
+	// Steps the machine until it halts, hits a breakpoint or errors out.
+	pub fn run_until_halt(&mut self) -> RunResult {
+		loop {
+			let at_breakpoint = self.breakpoints.get(self.regs.pc as usize).copied().unwrap_or(false);
+			if !self.halted && at_breakpoint {
+				return RunResult::Breakpoint(self.regs.pc);
+			}
+			match self.step() {
+				StepResult::Ran(_) => continue,
+				StepResult::Halted => return RunResult::Halted,
+				StepResult::Error(e) => return RunResult::Error(e),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{add, clear, con, end, store};
+
+	fn load(m: &mut Machine, mem: &[u16]) {
+		m.memory_mut()[..mem.len()].copy_from_slice(mem);
+	}
+
+	#[test]
+	fn step_runs_until_end_then_halts_without_re_executing() {
+		let mut m = Machine::new(0);
+		load(&mut m, &[clear(), add(3), end(), con(7)]);
+
+		assert!(matches!(m.step(), StepResult::Ran(_)));
+		assert!(matches!(m.step(), StepResult::Ran(_)));
+		assert!(!m.is_halted());
+		assert!(matches!(m.step(), StepResult::Ran(_)));
+		assert!(m.is_halted());
+
+		// END already ran; pc must not advance on further no-op steps.
+		let pc_after_halt = m.pc();
+		assert!(matches!(m.step(), StepResult::Halted));
+		assert_eq!(m.pc(), pc_after_halt);
+	}
+
+	#[test]
+	fn run_until_halt_stops_at_a_breakpoint_then_resumes() {
+		let mut m = Machine::new(0);
+		load(&mut m, &[clear(), store(3), end(), con(0)]);
+		m.set_breakpoint(1).unwrap();
+
+		match m.run_until_halt() {
+			RunResult::Breakpoint(pc) => assert_eq!(pc, 1),
+			_ => panic!("expected to stop at the breakpoint"),
+		}
+		assert!(!m.is_halted());
+
+		// A front-end steps past the breakpoint by hand, then continues.
+		assert!(matches!(m.step(), StepResult::Ran(_)));
+		assert!(matches!(m.run_until_halt(), RunResult::Halted));
+	}
+
+	#[test]
+	fn set_breakpoint_out_of_range_reports_an_error_instead_of_panicking() {
+		let mut m = Machine::new(0);
+		assert_eq!(m.set_breakpoint(MAX_MEM as u16), Err(Error::AddressOutOfRange(MAX_MEM as u16)));
+	}
+}
@@ -0,0 +1,137 @@
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+//
+// Copyright 2017 by Kevin Läufer <kevin.laeufer@rwth-aachen.de>
+
+// A `Program` is a named, persistable memory image: an entry `pc` plus
+// the assembled words. Previously the only image in existence was the
+// `mem` array literal in `main`; a `Program` can instead be assembled
+// once, saved to a compact binary blob, and re-run later without
+// recompiling the Rust binary.
+
+use std::fmt::Write as _;
+
+use super::{Instruction, Integer};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgramError {
+	Truncated,
+	OutOfBounds { offset: u16, len: usize, mem_len: usize },
+}
+
+impl std::fmt::Display for ProgramError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match self {
+			ProgramError::Truncated => write!(f, "truncated program image"),
+			ProgramError::OutOfBounds { offset, len, mem_len } =>
+				write!(f, "image of {} word(s) at offset {} does not fit into {}-word memory", len, offset, mem_len),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct Program {
+	pub entry: u16,
+	pub image: Vec<u16>,
+}
+
+impl Program {
+	pub fn new(entry: u16, image: Vec<u16>) -> Program {
+		Program { entry, image }
+	}
+
+	// Places this program's image into `mem` starting at `offset`, so
+	// multiple programs/data segments can be composed into one memory.
+	pub fn load_into(&self, mem: &mut [u16], offset: u16) -> Result<(), ProgramError> {
+		let mem_len = mem.len();
+		let out_of_bounds = || ProgramError::OutOfBounds { offset, len: self.image.len(), mem_len };
+		let start = offset as usize;
+		let end = start.checked_add(self.image.len()).ok_or_else(out_of_bounds)?;
+		let dest = mem.get_mut(start..end).ok_or_else(out_of_bounds)?;
+		dest.copy_from_slice(&self.image);
+		Ok(())
+	}
+
+	// A compact binary blob: a 2-byte little-endian entry pc followed by
+	// the image, one little-endian u16 per word.
+	pub fn serialize(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(2 + self.image.len() * 2);
+		bytes.extend_from_slice(&self.entry.to_le_bytes());
+		for w in &self.image {
+			bytes.extend_from_slice(&w.to_le_bytes());
+		}
+		bytes
+	}
+
+	pub fn deserialize(bytes: &[u8]) -> Result<Program, ProgramError> {
+		if bytes.len() < 2 || !(bytes.len() - 2).is_multiple_of(2) {
+			return Err(ProgramError::Truncated);
+		}
+		let entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+		let image = bytes[2..]
+			.chunks_exact(2)
+			.map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+			.collect();
+		Ok(Program::new(entry, image))
+	}
+
+	// A plain human-readable listing, mirroring `print_mem`'s
+	// `addr: bits (decimal | mnemonic)` layout.
+	pub fn listing(&self) -> String {
+		let mut out = String::new();
+		for (addr, w) in self.image.iter().enumerate() {
+			let _ = writeln!(out, "{0:04}: {1:016b} ({2:>6} | {3:<10})", addr, w,
+					Integer { w: *w }.to_string(),
+					Instruction { w: *w }.to_string());
+		}
+		out
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn serialize_then_deserialize_round_trips() {
+		let program = Program::new(3, vec![0x1234, 0xffff, 0x0001]);
+		let bytes = program.serialize();
+		let restored = Program::deserialize(&bytes).unwrap();
+		assert_eq!(restored.entry, program.entry);
+		assert_eq!(restored.image, program.image);
+	}
+
+	#[test]
+	fn deserialize_rejects_a_truncated_blob() {
+		assert_eq!(Program::deserialize(&[0x01]).unwrap_err(), ProgramError::Truncated);
+		assert_eq!(Program::deserialize(&[0x01, 0x00, 0x02]).unwrap_err(), ProgramError::Truncated);
+	}
+
+	#[test]
+	fn load_into_places_the_image_at_the_given_offset() {
+		let program = Program::new(0, vec![10, 20, 30]);
+		let mut mem = [0u16; 8];
+		program.load_into(&mut mem, 2).unwrap();
+		assert_eq!(&mem, &[0, 0, 10, 20, 30, 0, 0, 0]);
+	}
+
+	#[test]
+	fn load_into_reports_an_error_instead_of_panicking_when_the_image_does_not_fit() {
+		let program = Program::new(0, vec![1, 2, 3]);
+		let mut mem = [0u16; 4];
+		assert_eq!(
+			program.load_into(&mut mem, 2),
+			Err(ProgramError::OutOfBounds { offset: 2, len: 3, mem_len: 4 }),
+		);
+	}
+}